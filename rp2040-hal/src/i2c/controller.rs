@@ -4,20 +4,55 @@
 //! devices on the bus.
 //!
 //! We implement both the Embedded HAL 1.0 and legacy Embedded HAL 0.2 traits.
-//! Currently we only support 7-bit addresses, not 10-bit addresses.
+//! Both 7-bit and 10-bit addresses are supported; the legacy Embedded HAL 0.2
+//! traits and the `embedded_hal::i2c::I2c<SevenBitAddress>` impl only cover
+//! 7-bit addressing, while `embedded_hal::i2c::I2c<TenBitAddress>` shares the
+//! same transaction engine for 10-bit devices.
 
 use core::{marker::PhantomData, ops::Deref};
 use fugit::HertzU32;
 
+use embedded_hal::delay::DelayNs;
 use embedded_hal::i2c;
 use embedded_hal_0_2::blocking::i2c as i2c02;
 
-use super::{i2c_reserved_addr, Controller, Error, ValidPinScl, ValidPinSda, I2C};
+use super::{i2c_reserved_addr, AbortReason, Controller, Error, ValidPinScl, ValidPinSda, I2C};
 use crate::{
-    pac::{i2c0::RegisterBlock as Block, RESETS},
+    gpio::{AnyPin, PinId},
+    pac::{self, i2c0::RegisterBlock as Block, RESETS},
     resets::SubsystemReset,
 };
 
+/// Configuration for [`I2C::new_controller_with_config`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    /// Bus frequency.
+    pub freq: HertzU32,
+    /// Enable the RP2040's internal pull-up on the SDA pin.
+    ///
+    /// Useful for bringing up a bus on boards without external pull-up
+    /// resistors, but not a substitute for properly sized resistors on a
+    /// production board.
+    pub sda_pullup: bool,
+    /// Enable the RP2040's internal pull-up on the SCL pin.
+    ///
+    /// Useful for bringing up a bus on boards without external pull-up
+    /// resistors, but not a substitute for properly sized resistors on a
+    /// production board.
+    pub scl_pullup: bool,
+}
+
+impl Default for Config {
+    /// 100 kHz, no internal pull-ups.
+    fn default() -> Self {
+        Self {
+            freq: HertzU32::kHz(100),
+            sda_pullup: false,
+            scl_pullup: false,
+        }
+    }
+}
+
 // ============================================================================
 //
 // Inherent Methods
@@ -39,7 +74,30 @@ where
         resets: &mut RESETS,
         system_clock: HertzU32,
     ) -> Self {
-        let freq = freq.to_Hz();
+        Self::new_controller_with_config(
+            i2c,
+            sda_pin,
+            scl_pin,
+            Config {
+                freq,
+                ..Config::default()
+            },
+            resets,
+            system_clock,
+        )
+    }
+
+    /// Configures the I²C peripheral to work in controller mode, with the
+    /// bus frequency and internal SDA/SCL pull-ups given by `config`.
+    pub fn new_controller_with_config(
+        i2c: T,
+        sda_pin: Sda,
+        scl_pin: Scl,
+        config: Config,
+        resets: &mut RESETS,
+        system_clock: HertzU32,
+    ) -> Self {
+        let freq = config.freq.to_Hz();
         assert!(freq <= 1_000_000);
         assert!(freq > 0);
 
@@ -113,12 +171,95 @@ where
         // Enable I²C block
         i2c.ic_enable.write(|w| w.enable().enabled());
 
+        if config.sda_pullup || config.scl_pullup {
+            // Safety: we only touch the pad control bits for our own SDA/SCL
+            // pins.
+            let pads_bank0 = unsafe { &*pac::PADS_BANK0::ptr() };
+            if config.sda_pullup {
+                pads_bank0.gpio[Sda::Id::DYN.num as usize]
+                    .modify(|_, w| w.pue().set_bit().pde().clear_bit());
+            }
+            if config.scl_pullup {
+                pads_bank0.gpio[Scl::Id::DYN.num as usize]
+                    .modify(|_, w| w.pue().set_bit().pde().clear_bit());
+            }
+        }
+
         Self {
             i2c,
             pins: (sda_pin, scl_pin),
             mode: PhantomData,
         }
     }
+
+    /// Recover a bus wedged by a slave holding SDA low (e.g. one that was
+    /// reset mid-transfer).
+    ///
+    /// Temporarily switches the SDA/SCL pins from their I²C function to
+    /// plain GPIO, clocks out up to 9 manual SCL pulses while SDA reads low
+    /// to let the slave finish its current byte and release the bus, then
+    /// issues a manual STOP condition before restoring the I²C function and
+    /// re-enabling the block.
+    ///
+    /// This does not power-cycle the bus, so a slave holding SDA low for
+    /// reasons other than a stuck byte boundary (e.g. a dead short) will
+    /// not be recovered.
+    pub fn recover_bus(&mut self, delay: &mut impl DelayNs) {
+        let sda_num = Sda::Id::DYN.num;
+        let scl_num = Scl::Id::DYN.num;
+
+        self.i2c.ic_enable.write(|w| w.enable().disabled());
+
+        // Safety: we briefly reach past our owned pins to bit-bang them as
+        // plain GPIO; the IO_BANK0/SIO function-select bits we touch are
+        // restored to their I²C configuration before we return.
+        let io_bank0 = unsafe { &*pac::IO_BANK0::ptr() };
+        let sio = unsafe { &*pac::SIO::ptr() };
+
+        io_bank0.gpio[sda_num as usize]
+            .gpio_ctrl
+            .write(|w| w.funcsel().sio());
+        io_bank0.gpio[scl_num as usize]
+            .gpio_ctrl
+            .write(|w| w.funcsel().sio());
+
+        let release = |num: u8| unsafe { sio.gpio_oe_clr.write(|w| w.bits(1 << num)) };
+        let drive_low = |num: u8| unsafe {
+            sio.gpio_out_clr.write(|w| w.bits(1 << num));
+            sio.gpio_oe_set.write(|w| w.bits(1 << num));
+        };
+        let is_high = |num: u8| sio.gpio_in.read().bits() & (1 << num) != 0;
+
+        release(sda_num);
+        release(scl_num);
+
+        for _ in 0..9 {
+            if is_high(sda_num) {
+                break;
+            }
+            drive_low(scl_num);
+            delay.delay_us(5);
+            release(scl_num);
+            delay.delay_us(5);
+        }
+
+        // Manually issue a STOP condition: SDA low -> high while SCL is high.
+        drive_low(sda_num);
+        delay.delay_us(5);
+        release(scl_num);
+        delay.delay_us(5);
+        release(sda_num);
+        delay.delay_us(5);
+
+        io_bank0.gpio[sda_num as usize]
+            .gpio_ctrl
+            .write(|w| w.funcsel().i2c());
+        io_bank0.gpio[scl_num as usize]
+            .gpio_ctrl
+            .write(|w| w.funcsel().i2c());
+
+        self.i2c.ic_enable.write(|w| w.enable().enabled());
+    }
 }
 
 impl<T: Deref<Target = Block>, PINS> I2C<T, PINS, Controller> {
@@ -135,6 +276,25 @@ impl<T: Deref<Target = Block>, PINS> I2C<T, PINS, Controller> {
         address: u8,
         opt_tx_empty: Option<bool>,
         opt_rx_empty: Option<bool>,
+    ) -> Result<(), Error> {
+        Self::validate_addr(address as u16, false, opt_tx_empty, opt_rx_empty)
+    }
+
+    /// Validate user-supplied arguments, for either a 7-bit or 10-bit address.
+    ///
+    /// If the arguments are not valid, an Error is returned.
+    ///
+    /// Checks that:
+    ///
+    /// * The address is in range for the addressing mode (`0x7F` for 7-bit,
+    ///   `0x3FF` for 10-bit), and, for 7-bit addressing, not reserved
+    /// * The `opt_tx_empty` arg is not `Some(true)`
+    /// * The `opt_rx_empty` arg is not `Some(true)`
+    fn validate_addr(
+        address: u16,
+        ten_bit: bool,
+        opt_tx_empty: Option<bool>,
+        opt_rx_empty: Option<bool>,
     ) -> Result<(), Error> {
         // validate tx parameters if present
         if opt_tx_empty.unwrap_or(false) {
@@ -147,31 +307,61 @@ impl<T: Deref<Target = Block>, PINS> I2C<T, PINS, Controller> {
         }
 
         // validate address
-        if address >= 0x80 {
-            Err(Error::AddressOutOfRange(address as u16))
-        } else if i2c_reserved_addr(address as u16) {
-            Err(Error::AddressReserved(address as u16))
-        } else {
-            Ok(())
+        if ten_bit {
+            if address > 0x3FF {
+                return Err(Error::AddressOutOfRange(address));
+            }
+        } else if address >= 0x80 {
+            return Err(Error::AddressOutOfRange(address));
+        } else if i2c_reserved_addr(address) {
+            return Err(Error::AddressReserved(address));
         }
+        Ok(())
     }
 
     fn setup(&mut self, address: u8) {
+        self.setup_addr(address as u16, false);
+    }
+
+    /// Program `IC_TAR` with `address`, switching the controller's
+    /// addressing mode (`IC_10BITADDR_MASTER`) to match.
+    fn setup_addr(&mut self, address: u16, ten_bit: bool) {
         self.i2c.ic_enable.write(|w| w.enable().disabled());
+        self.i2c.ic_con.modify(|_, w| {
+            if ten_bit {
+                w.ic_10bitaddr_master().addr_10bits()
+            } else {
+                w.ic_10bitaddr_master().addr_7bits()
+            }
+        });
         self.i2c
             .ic_tar
-            .write(|w| unsafe { w.ic_tar().bits(address as u16) });
+            .write(|w| unsafe { w.ic_tar().bits(address) });
         self.i2c.ic_enable.write(|w| w.enable().enabled());
     }
 
-    fn read_and_clear_abort_reason(&mut self) -> Option<u32> {
+    /// Validate `address` and `bytes`/`buffer` emptiness, then program
+    /// `IC_TAR`. Used by the [`dma`](super::dma) transfer paths.
+    pub(super) fn validate_and_setup(
+        &mut self,
+        address: u8,
+        tx_empty: bool,
+        rx_empty: bool,
+    ) -> Result<(), Error> {
+        Self::validate(address, Some(tx_empty), Some(rx_empty))?;
+        self.setup(address);
+        Ok(())
+    }
+
+    fn read_and_clear_abort_reason(&mut self) -> Option<AbortReason> {
         let abort_reason = self.i2c.ic_tx_abrt_source.read().bits();
         if abort_reason != 0 {
             // Note clearing the abort flag also clears the reason, and
             // this instance of flag is clear-on-read! Note also the
             // IC_CLR_TX_ABRT register always reads as 0.
             self.i2c.ic_clr_tx_abrt.read();
-            Some(abort_reason)
+
+            Some(AbortReason::decode(abort_reason))
         } else {
             None
         }
@@ -457,3 +647,36 @@ impl<T: Deref<Target = Block>, PINS> i2c::I2c<i2c::SevenBitAddress> for I2C<T, P
         Ok(())
     }
 }
+
+impl<T: Deref<Target = Block>, PINS> i2c::I2c<i2c::TenBitAddress> for I2C<T, PINS, Controller> {
+    fn write(&mut self, addr: i2c::TenBitAddress, bytes: &[u8]) -> Result<(), Self::Error> {
+        Self::validate_addr(addr, true, Some(bytes.is_empty()), None)?;
+        self.setup_addr(addr, true);
+
+        self.write_internal(bytes, true)
+    }
+
+    fn read(&mut self, addr: i2c::TenBitAddress, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        Self::validate_addr(addr, true, None, Some(buffer.is_empty()))?;
+        self.setup_addr(addr, true);
+
+        self.read_internal(buffer, true, true)
+    }
+
+    fn transaction(
+        &mut self,
+        address: i2c::TenBitAddress,
+        operations: &mut [i2c::Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        Self::validate_addr(address, true, None, None)?;
+        self.setup_addr(address, true);
+        for i in 0..operations.len() {
+            let last = i == operations.len() - 1;
+            match &mut operations[i] {
+                i2c::Operation::Read(buf) => self.read_internal(buf, false, last)?,
+                i2c::Operation::Write(buf) => self.write_internal(buf, last)?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,458 @@
+//! # DMA-backed asynchronous I²C controller transfers
+//!
+//! The blocking API in [`super::controller`] drives the `IC_DATA_CMD` FIFO by
+//! spinning on `tx_fifo_full` and `ic_rxflr`. For large buffers it is often
+//! preferable to let the RP2040 DMA engine push and pull the FIFO instead,
+//! freeing the CPU to do other work while the transfer is in flight.
+//!
+//! [`I2C::with_dma`](super::I2C::with_dma) attaches a TX and an RX DMA
+//! channel to a controller-mode [`I2C`](super::I2C), producing an
+//! [`I2CDma`] that exposes `async fn` `read`/`write`/`write_read` plus the
+//! `embedded-hal-async` [`I2c` trait](embedded_hal_async::i2c::I2c).
+//!
+//! Transfer completion (and abort) is signalled from the I²C interrupt, so
+//! users must call [`I2CDma::on_interrupt`] from their `I2C0_IRQ`/`I2C1_IRQ`
+//! handler.
+
+use core::future::poll_fn;
+use core::ops::Deref;
+use core::sync::atomic::{AtomicU32, AtomicU8, Ordering};
+use core::task::Poll;
+
+use embedded_hal_async::i2c as i2c_async;
+
+use crate::dma::{Channel, ReadTarget, SingleChannel, WriteTarget};
+use crate::pac::i2c0::RegisterBlock as Block;
+
+use super::{AbortReason, Controller, Error, I2C};
+
+const NONE: u8 = 0;
+const PENDING: u8 = 1;
+const ABORTED: u8 = 2;
+const DONE: u8 = 3;
+
+/// Shared state updated from the I²C interrupt and polled by the async
+/// transfer futures, one slot per I²C block (0 = `I2C0`, 1 = `I2C1`).
+struct AsyncState {
+    status: AtomicU8,
+    abort_reason: AtomicU32,
+    waker: critical_section::Mutex<core::cell::Cell<Option<core::task::Waker>>>,
+}
+
+impl AsyncState {
+    const fn new() -> Self {
+        Self {
+            status: AtomicU8::new(NONE),
+            abort_reason: AtomicU32::new(0),
+            waker: critical_section::Mutex::new(core::cell::Cell::new(None)),
+        }
+    }
+}
+
+static STATE: [AsyncState; 2] = [AsyncState::new(), AsyncState::new()];
+
+/// Aborts both DMA channels and resets the status to `NONE` whenever it is
+/// dropped, including when the driving future was dropped before completion
+/// instead of running to `Poll::Ready`, so a cancelled transfer can never
+/// leave the channels running into the next one.
+struct AbortOnDrop<'a, TxCh: SingleChannel, RxCh: SingleChannel> {
+    state: &'static AsyncState,
+    tx_ch: &'a mut Channel<TxCh>,
+    rx_ch: &'a mut Channel<RxCh>,
+}
+
+impl<'a, TxCh: SingleChannel, RxCh: SingleChannel> Drop for AbortOnDrop<'a, TxCh, RxCh> {
+    fn drop(&mut self) {
+        // Always abort unconditionally, rather than only when `status` is
+        // still `PENDING`: the interrupt can flip it to `ABORTED`/`DONE`
+        // concurrently with cancellation, and in that race the channels
+        // may not have been aborted/reset yet either.
+        self.state.status.store(NONE, Ordering::Release);
+        self.tx_ch.abort();
+        self.rx_ch.abort();
+    }
+}
+
+/// Identifies which I²C block (`I2C0` or `I2C1`) a PAC handle is, so the
+/// interrupt state above can be indexed without the index leaking into every
+/// generic parameter list.
+///
+/// # Safety
+///
+/// `INDEX` must match the interrupt-vector index (0 for `I2C0`, 1 for
+/// `I2C1`) of the implementing PAC type.
+pub unsafe trait I2CDevice: Deref<Target = Block> {
+    /// `0` for `I2C0`, `1` for `I2C1`.
+    const INDEX: usize;
+}
+
+unsafe impl I2CDevice for crate::pac::I2C0 {
+    const INDEX: usize = 0;
+}
+
+unsafe impl I2CDevice for crate::pac::I2C1 {
+    const INDEX: usize = 1;
+}
+
+/// DMA pacing signal (`DREQ`) numbers for the I²C TX/RX FIFOs, indexed by
+/// [`I2CDevice::INDEX`].
+const TX_DREQS: [u8; 2] = [32, 34];
+const RX_DREQS: [u8; 2] = [33, 35];
+
+/// The `IC_DATA_CMD` register, as a DMA read/write target.
+///
+/// Writes push 16-bit command words (data plus the `RESTART`/`STOP`/`CMD`
+/// bits); reads pull the received byte out of the low 8 bits. Transfers are
+/// paced by the I²C block's own `DREQ` so a multi-byte transfer can't outrun
+/// the 16-entry hardware FIFO.
+#[derive(Clone, Copy)]
+struct DataCmd {
+    block: *const Block,
+    index: usize,
+}
+
+unsafe impl WriteTarget for DataCmd {
+    type TransmittedWord = u16;
+
+    fn tx_treq(&self) -> Option<u8> {
+        Some(TX_DREQS[self.index])
+    }
+
+    fn tx_address_count(&mut self) -> (u32, u32) {
+        (
+            unsafe { &*self.block }.ic_data_cmd.as_ptr() as u32,
+            u32::MAX,
+        )
+    }
+
+    fn tx_increment(&self) -> bool {
+        false
+    }
+}
+
+unsafe impl ReadTarget for DataCmd {
+    type ReceivedWord = u8;
+
+    fn rx_treq(&self) -> Option<u8> {
+        Some(RX_DREQS[self.index])
+    }
+
+    fn rx_address_count(&self) -> (u32, u32) {
+        (
+            unsafe { &*self.block }.ic_data_cmd.as_ptr() as u32,
+            u32::MAX,
+        )
+    }
+
+    fn rx_increment(&self) -> bool {
+        false
+    }
+}
+
+/// Command word for a DMA-streamed read: `cmd().read()` plus the
+/// `RESTART`/`STOP` bits for the first/last byte of the transfer.
+fn read_cmd_word(first: bool, last: bool) -> u16 {
+    const CMD_READ: u16 = 1 << 8;
+    const RESTART: u16 = 1 << 10;
+    const STOP: u16 = 1 << 9;
+    let mut word = CMD_READ;
+    if first {
+        word |= RESTART;
+    }
+    if last {
+        word |= STOP;
+    }
+    word
+}
+
+/// Command word for a DMA-streamed write: the data byte plus `STOP` on the
+/// last byte of the transfer.
+fn write_cmd_word(byte: u8, last_and_stop: bool) -> u16 {
+    const STOP: u16 = 1 << 9;
+    let mut word = byte as u16;
+    if last_and_stop {
+        word |= STOP;
+    }
+    word
+}
+
+/// An [`I2C`] controller whose reads and writes are driven by DMA, with
+/// completion signalled asynchronously via the I²C interrupt.
+pub struct I2CDma<T, PINS, TxCh, RxCh> {
+    i2c: I2C<T, PINS, Controller>,
+    tx_ch: Channel<TxCh>,
+    rx_ch: Channel<RxCh>,
+}
+
+impl<T: I2CDevice, PINS> I2C<T, PINS, Controller> {
+    /// Attaches a TX and an RX DMA channel, returning an [`I2CDma`] that
+    /// drives reads and writes via DMA instead of CPU-polled FIFO access.
+    pub fn with_dma<TxCh: SingleChannel, RxCh: SingleChannel>(
+        self,
+        tx_ch: Channel<TxCh>,
+        rx_ch: Channel<RxCh>,
+    ) -> I2CDma<T, PINS, TxCh, RxCh> {
+        I2CDma {
+            i2c: self,
+            tx_ch,
+            rx_ch,
+        }
+    }
+}
+
+impl<T, PINS, TxCh, RxCh> I2CDma<T, PINS, TxCh, RxCh>
+where
+    T: I2CDevice,
+    TxCh: SingleChannel,
+    RxCh: SingleChannel,
+{
+    /// Releases the DMA channels and returns the plain blocking [`I2C`].
+    pub fn free(self) -> (I2C<T, PINS, Controller>, Channel<TxCh>, Channel<RxCh>) {
+        (self.i2c, self.tx_ch, self.rx_ch)
+    }
+
+    /// Call this from your `I2C0_IRQ`/`I2C1_IRQ` interrupt handler.
+    ///
+    /// Masks the interrupt source, stashes the abort reason (if any) and
+    /// wakes the task driving the in-flight transfer, if there is one.
+    pub fn on_interrupt(i2c: &Block) {
+        let index = if core::ptr::eq(i2c, unsafe { &*crate::pac::I2C0::ptr() }) {
+            0
+        } else {
+            1
+        };
+        let state = &STATE[index];
+
+        let raw_intr = i2c.ic_raw_intr_stat.read();
+        let fired = if raw_intr.tx_abrt().is_active() {
+            let reason = i2c.ic_tx_abrt_source.read().bits();
+            i2c.ic_clr_tx_abrt.read();
+            state.abort_reason.store(reason, Ordering::Relaxed);
+            state.status.store(ABORTED, Ordering::Release);
+            true
+        } else if raw_intr.stop_det().is_active() {
+            // STOP_DET only fires once an actual stop condition has been
+            // seen on the bus, so unlike TX_EMPTY it can't pre-fire before
+            // the DMA has loaded the FIFO.
+            i2c.ic_clr_stop_det.read();
+            state.status.store(DONE, Ordering::Release);
+            true
+        } else {
+            false
+        };
+        if !fired {
+            return;
+        }
+
+        // Mask only what `arm()` unmasked; `.write()` would otherwise reset
+        // every other field to its hardware default, which re-enables
+        // sources like TX_EMPTY that must stay masked.
+        // In `IC_INTR_MASK`, `enabled()` (0) masks a source and `disabled()`
+        // (1) unmasks it.
+        i2c.ic_intr_mask
+            .modify(|_, w| w.m_tx_abrt().enabled().m_stop_det().enabled());
+
+        critical_section::with(|cs| {
+            if let Some(waker) = state.waker.borrow(cs).take() {
+                waker.wake();
+            }
+        });
+    }
+
+    fn arm(&mut self) {
+        let state = &STATE[T::INDEX];
+        state.status.store(PENDING, Ordering::Release);
+        // Only TX_ABRT and STOP_DET are edge/one-shot: both only assert once
+        // something has actually happened on the bus. TX_EMPTY is level
+        // triggered on "FIFO at/below threshold", which is already true
+        // before the DMA has pushed anything, so it must not be unmasked
+        // here or completion can be signalled before any data has moved.
+        // `write_with_zero` masks every other source (0 = masked); the I²C
+        // TX/RX DREQs are only raised once `IC_DMA_CR` enables them.
+        // Safety: all-zero is a valid `IC_INTR_MASK` value (everything masked).
+        unsafe {
+            self.i2c.i2c.ic_intr_mask.write_with_zero(|w| {
+                w.m_tx_abrt().disabled();
+                w.m_stop_det().disabled()
+            });
+        }
+        self.i2c
+            .i2c
+            .ic_dma_cr
+            .write(|w| w.tdmae().enabled().rdmae().enabled());
+    }
+
+    async fn wait_for_completion(&mut self) -> Result<(), Error> {
+        let state = &STATE[T::INDEX];
+        // Guards against the future being dropped (e.g. raced against a
+        // timeout) before a result ever arrives: without this, the DMA
+        // channels would keep running unattended and the next transfer
+        // would call `arm()` and restart them while the stale one is still
+        // in flight, misattributing its eventual TX_ABRT/STOP_DET.
+        let mut guard = AbortOnDrop {
+            state,
+            tx_ch: &mut self.tx_ch,
+            rx_ch: &mut self.rx_ch,
+        };
+        poll_fn(|cx| {
+            // Register the waker *before* checking status: if we checked
+            // first, the interrupt could fire, store a result and take a
+            // (still empty) waker in the gap between our check and the
+            // registration below, losing the wakeup and hanging forever.
+            critical_section::with(|cs| state.waker.borrow(cs).set(Some(cx.waker().clone())));
+
+            match state.status.load(Ordering::Acquire) {
+                ABORTED => {
+                    state.status.store(NONE, Ordering::Relaxed);
+                    guard.tx_ch.abort();
+                    guard.rx_ch.abort();
+                    Poll::Ready(Err(Error::Abort(AbortReason::decode(
+                        state.abort_reason.load(Ordering::Relaxed),
+                    ))))
+                }
+                DONE => {
+                    state.status.store(NONE, Ordering::Relaxed);
+                    Poll::Ready(Ok(()))
+                }
+                _ => Poll::Pending,
+            }
+        })
+        .await
+    }
+
+    /// Writes `bytes` to the device at `address`, via DMA.
+    pub async fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Error> {
+        self.i2c
+            .validate_and_setup(address, bytes.is_empty(), false)?;
+        self.arm();
+
+        let last = bytes.len().saturating_sub(1);
+        let words = bytes
+            .iter()
+            .enumerate()
+            .map(|(i, &b)| write_cmd_word(b, i == last));
+        let data_cmd = DataCmd {
+            block: &*self.i2c.i2c as *const Block,
+            index: T::INDEX,
+        };
+        self.tx_ch.start_write(words, data_cmd);
+
+        self.wait_for_completion().await
+    }
+
+    /// Reads into `buffer` from the device at `address`, via DMA.
+    pub async fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Error> {
+        self.i2c
+            .validate_and_setup(address, false, buffer.is_empty())?;
+        self.arm();
+
+        let last = buffer.len().saturating_sub(1);
+        let cmds = (0..buffer.len()).map(|i| read_cmd_word(i == 0, i == last));
+        let data_cmd = DataCmd {
+            block: &*self.i2c.i2c as *const Block,
+            index: T::INDEX,
+        };
+        self.tx_ch.start_write(cmds, data_cmd);
+        self.rx_ch.start_read(data_cmd, buffer);
+
+        self.wait_for_completion().await
+    }
+
+    /// Writes `bytes` then reads into `buffer`, as a single transaction, via DMA.
+    pub async fn write_read(
+        &mut self,
+        address: u8,
+        bytes: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<(), Error> {
+        self.i2c
+            .validate_and_setup(address, bytes.is_empty(), buffer.is_empty())?;
+        self.arm();
+
+        let last_read = buffer.len().saturating_sub(1);
+        let writes = bytes.iter().map(|&b| write_cmd_word(b, false));
+        let reads = (0..buffer.len()).map(|i| read_cmd_word(i == 0, i == last_read));
+        let data_cmd = DataCmd {
+            block: &*self.i2c.i2c as *const Block,
+            index: T::INDEX,
+        };
+        self.tx_ch.start_write(writes.chain(reads), data_cmd);
+        self.rx_ch.start_read(data_cmd, buffer);
+
+        self.wait_for_completion().await
+    }
+}
+
+impl<T, PINS, TxCh, RxCh> i2c_async::ErrorType for I2CDma<T, PINS, TxCh, RxCh> {
+    type Error = Error;
+}
+
+impl<T, PINS, TxCh, RxCh> i2c_async::I2c for I2CDma<T, PINS, TxCh, RxCh>
+where
+    T: I2CDevice,
+    TxCh: SingleChannel,
+    RxCh: SingleChannel,
+{
+    async fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [i2c_async::Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        // The common "write a register address, then read its value"
+        // pattern compiles down to one or more `Write`s followed by a
+        // single `Read` (this is exactly the shape the trait's default
+        // `write_read` produces). Drive that shape as one combined DMA
+        // burst, with a single Sr between the writes and the read and one
+        // Sp at the end, mirroring `Self::write_read` above. A DMA
+        // transfer can only target one destination buffer, so any other
+        // shape (more than one `Read`, or a `Read` before a `Write`)
+        // still runs as independent full transactions below.
+        let combinable = operations.len() > 1
+            && matches!(operations.last(), Some(i2c_async::Operation::Read(_)))
+            && operations[..operations.len() - 1]
+                .iter()
+                .all(|op| matches!(op, i2c_async::Operation::Write(_)));
+
+        if combinable {
+            let (writes, last) = operations.split_at_mut(operations.len() - 1);
+            let buffer = match &mut last[0] {
+                i2c_async::Operation::Read(buf) => buf,
+                _ => unreachable!(),
+            };
+
+            let tx_empty = writes.iter().all(|op| match op {
+                i2c_async::Operation::Write(buf) => buf.is_empty(),
+                _ => unreachable!(),
+            });
+            self.i2c
+                .validate_and_setup(address, tx_empty, buffer.is_empty())?;
+            self.arm();
+
+            let last_read = buffer.len().saturating_sub(1);
+            let tx_words = writes
+                .iter()
+                .flat_map(|op| match op {
+                    i2c_async::Operation::Write(buf) => buf.iter().copied(),
+                    _ => unreachable!(),
+                })
+                .map(|b| write_cmd_word(b, false));
+            let rx_words = (0..buffer.len()).map(|i| read_cmd_word(i == 0, i == last_read));
+            let data_cmd = DataCmd {
+                block: &*self.i2c.i2c as *const Block,
+                index: T::INDEX,
+            };
+            self.tx_ch.start_write(tx_words.chain(rx_words), data_cmd);
+            self.rx_ch.start_read(data_cmd, buffer);
+
+            return self.wait_for_completion().await;
+        }
+
+        for operation in operations {
+            match operation {
+                i2c_async::Operation::Read(buf) => self.read(address, buf).await?,
+                i2c_async::Operation::Write(buf) => self.write(address, buf).await?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,148 @@
+//! # Inter-Integrated Circuit (I²C) Bus
+//!
+//! This module implements the I²C peripheral driver for the RP2040. The
+//! peripheral can be configured to act either as a bus [`Controller`] (the
+//! device initiating transfers, formerly called "master") by using
+//! [`I2C::new_controller`], or as a bus `Peripheral` (formerly called
+//! "slave").
+//!
+//! See [`crate::i2c::controller`] for the controller-mode API.
+
+use core::{marker::PhantomData, ops::Deref};
+
+use crate::{
+    gpio::{AnyPin, FunctionI2C},
+    pac::i2c0::RegisterBlock as Block,
+};
+
+pub mod controller;
+pub mod dma;
+
+/// Marker type for an I²C block configured as bus controller.
+pub struct Controller;
+
+/// Marker type for an I²C block configured as bus peripheral.
+pub struct Peripheral;
+
+/// I²C peripheral driver.
+///
+/// `T` is the underlying PAC peripheral (`I2C0` or `I2C1`), `PINS` is the
+/// SDA/SCL pin pair in use, and `MODE` is either [`Controller`] or
+/// `Peripheral`.
+pub struct I2C<T, PINS, MODE = Controller> {
+    pub(super) i2c: T,
+    pub(super) pins: PINS,
+    pub(super) mode: PhantomData<MODE>,
+}
+
+impl<T, PINS, MODE> I2C<T, PINS, MODE> {
+    /// Releases the underlying peripheral and pins.
+    pub fn free(self) -> (T, PINS) {
+        (self.i2c, self.pins)
+    }
+}
+
+impl<T: Deref<Target = Block>, PINS, MODE> I2C<T, PINS, MODE> {
+    fn tx_fifo_full(&self) -> bool {
+        self.i2c.ic_status.read().tfnf().bit_is_clear()
+    }
+}
+
+/// A pin that can be used as SDA on a given I²C block.
+pub trait ValidPinSda<I2C>: AnyPin<Function = FunctionI2C> {}
+
+/// A pin that can be used as SCL on a given I²C block.
+pub trait ValidPinScl<I2C>: AnyPin<Function = FunctionI2C> {}
+
+/// The reason an I²C transfer was aborted by the controller hardware.
+///
+/// This is a decoded view over the raw `IC_TX_ABRT_SOURCE` bitfield, see
+/// [`Error::Abort`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AbortReason {
+    /// The device did not acknowledge. Covers a plain 7-bit address NACK,
+    /// the 10-bit address and general-call variants, and a NACK of a
+    /// transmitted data byte.
+    NoAcknowledge,
+    /// The controller lost arbitration of the bus to another controller.
+    ArbitrationLoss,
+    /// An abort occurred for a reason not otherwise decoded here. The raw
+    /// `IC_TX_ABRT_SOURCE` value is preserved.
+    Other(u32),
+}
+
+impl AbortReason {
+    /// Decode a raw, non-zero `IC_TX_ABRT_SOURCE` value. Shared by the
+    /// blocking transfer engine in [`controller`] and the DMA transfer
+    /// futures in [`dma`].
+    fn decode(raw: u32) -> Self {
+        // Bit positions within IC_TX_ABRT_SOURCE, see the RP2040 datasheet.
+        const ABRT_7B_ADDR_NOACK: u32 = 1 << 0;
+        const ABRT_10ADDR1_NOACK: u32 = 1 << 1;
+        const ABRT_10ADDR2_NOACK: u32 = 1 << 2;
+        const ABRT_TXDATA_NOACK: u32 = 1 << 3;
+        const ABRT_GCALL_NOACK: u32 = 1 << 4;
+        const ARB_LOST: u32 = 1 << 12;
+        const NOACK: u32 = ABRT_7B_ADDR_NOACK
+            | ABRT_10ADDR1_NOACK
+            | ABRT_10ADDR2_NOACK
+            | ABRT_TXDATA_NOACK
+            | ABRT_GCALL_NOACK;
+
+        if raw & NOACK != 0 {
+            AbortReason::NoAcknowledge
+        } else if raw & ARB_LOST != 0 {
+            AbortReason::ArbitrationLoss
+        } else {
+            AbortReason::Other(raw)
+        }
+    }
+}
+
+/// I²C error
+#[derive(Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Error {
+    /// I²C abort with error
+    Abort(AbortReason),
+    /// User passed in a read buffer that was 0 length
+    ///
+    /// This is a limitation of the RP2040 I2C peripheral. If you have a
+    /// use-case where you need to read 0 bytes, please open an issue.
+    InvalidReadBufferLength,
+    /// User passed in a write buffer that was 0 length
+    ///
+    /// This is a limitation of the RP2040 I2C peripheral. If you have a
+    /// use-case where you need to write 0 bytes, please open an issue.
+    InvalidWriteBufferLength,
+    /// Target i2c address is out of range
+    AddressOutOfRange(u16),
+    /// Target i2c address is reserved
+    AddressReserved(u16),
+}
+
+impl embedded_hal::i2c::Error for Error {
+    fn kind(&self) -> embedded_hal::i2c::ErrorKind {
+        use embedded_hal::i2c::{ErrorKind, NoAcknowledgeSource};
+
+        match self {
+            Error::Abort(AbortReason::NoAcknowledge) => {
+                ErrorKind::NoAcknowledge(NoAcknowledgeSource::Unknown)
+            }
+            Error::Abort(AbortReason::ArbitrationLoss) => ErrorKind::ArbitrationLoss,
+            Error::Abort(AbortReason::Other(_)) => ErrorKind::Other,
+            Error::InvalidReadBufferLength
+            | Error::InvalidWriteBufferLength
+            | Error::AddressOutOfRange(_)
+            | Error::AddressReserved(_) => ErrorKind::Other,
+        }
+    }
+}
+
+/// Check if the given address is reserved for special I²C functions (see
+/// the RP2040 datasheet, section 4.3.1.1).
+pub(super) fn i2c_reserved_addr(addr: u16) -> bool {
+    (addr & 0x78) == 0 || (addr & 0x78) == 0x78
+}